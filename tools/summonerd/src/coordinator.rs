@@ -1,38 +1,110 @@
-use std::{cmp, collections::HashMap, time::Duration};
+use std::{
+    cmp,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
 use futures::FutureExt;
 use penumbra_keys::Address;
 use penumbra_num::Amount;
-use tokio::sync::mpsc::{self};
-use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::{
+    wrappers::{IntervalStream, ReceiverStream},
+    StreamExt,
+};
+use tokio_util::sync::CancellationToken;
 
+// This module leans on the following from `participant` and `storage`, which land alongside it:
+// `Participant: Clone`, `Participant::watch_position(watch::Receiver<QueuePosition>)`, and
+// `Storage::{requeue, persist_queued_participant, load_queued_participants,
+// remove_queued_participant}`. Reviewing this file in isolation will not build; review it
+// together with those companion changes.
 use crate::{participant::Participant, phase::Phase, storage::Storage};
 
+/// The grace period the coordinator will wait, once shutdown has been requested, for an
+/// in-flight contribution to finish and be committed to storage.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// A participant's current position in the ceremony queue.
+///
+/// This is broadcast over a `watch` channel rather than pushed as a one-shot message, so that a
+/// participant always sees their latest position, read at its own pace, without the coordinator
+/// having to send (and the participant having to buffer) every intermediate update.
+#[derive(Debug, Clone, Copy)]
+pub struct QueuePosition {
+    /// This participant's rank in the queue, where `0` is the current contributor.
+    pub index: u32,
+    /// The total number of participants currently queued.
+    pub len: u32,
+    /// The bid of whoever is currently contributing (or about to, if `index == 0`).
+    pub contributor_bid: Amount,
+    /// This participant's own bid.
+    pub your_bid: Amount,
+}
+
+/// Tunables for defending a public ceremony against connection churn and notification spam.
+///
+/// None of this is required for correctness: it exists purely to bound how much work a
+/// misbehaving (or just chatty) set of clients can force the coordinator to do.
+#[derive(Debug, Clone, Copy)]
+pub struct CoordinatorConfig {
+    /// The size of the new-participant channel buffer.
+    pub backlog_size: usize,
+    /// The minimum time that must pass between two `NewParticipant` events for the same address
+    /// before the later one is accepted; faster reconnects are dropped rather than queued.
+    pub min_reconnect_interval: Duration,
+    /// The minimum time between `update_positions` broadcasts triggered by a ranking change.
+    /// Bursts of changes within a window are collapsed into a single broadcast; the periodic
+    /// liveness tick still guarantees everyone's position eventually catches up.
+    pub min_inform_interval: Duration,
+    /// How often the coordinator ticks to check for dead connections and stalled contributors.
+    pub tick_period: Duration,
+}
+
+impl Default for CoordinatorConfig {
+    fn default() -> Self {
+        Self {
+            backlog_size: 9001,
+            min_reconnect_interval: Duration::from_secs(5),
+            min_inform_interval: Duration::from_millis(500),
+            tick_period: Duration::from_secs(30),
+        }
+    }
+}
+
 struct ContributionHandler {
     storage: Storage,
     start_contribution_rx: mpsc::Receiver<(Address, Participant)>,
     done_contribution_tx: mpsc::Sender<()>,
+    shutdown: CancellationToken,
+    abort_rx: mpsc::Receiver<()>,
 }
 
 impl ContributionHandler {
     pub fn new(
         storage: Storage,
+        shutdown: CancellationToken,
     ) -> (
         Self,
         mpsc::Sender<(Address, Participant)>,
         mpsc::Receiver<()>,
+        mpsc::Sender<()>,
     ) {
         let (start_contribution_tx, start_contribution_rx) = mpsc::channel(1);
         let (done_contribution_tx, done_contribution_rx) = mpsc::channel(1);
+        let (abort_tx, abort_rx) = mpsc::channel(1);
         (
             Self {
                 storage,
                 start_contribution_rx,
                 done_contribution_tx,
+                shutdown,
+                abort_rx,
             },
             start_contribution_tx,
             done_contribution_rx,
+            abort_tx,
         )
     }
 
@@ -49,7 +121,13 @@ impl ContributionHandler {
             };
             tracing::debug!(?who, "waiting for contribution");
             self.contribute::<P>(who, participant).await?;
+            // Always let the coordinator know this slot is free, whether the contribution
+            // landed, struck, or was re-queued, before we consider stopping.
             self.done_contribution_tx.send(()).await?;
+            if self.shutdown.is_cancelled() {
+                tracing::debug!("shutdown requested, stopping contribution handler");
+                return Ok(());
+            }
         }
     }
 
@@ -59,19 +137,47 @@ impl ContributionHandler {
         contributor: Address,
         participant: Participant,
     ) -> Result<()> {
-        match tokio::time::timeout(
-            Duration::from_secs(P::CONTRIBUTION_TIME_SECS),
-            self.contribute_inner::<P>(contributor, participant),
-        )
-        .await
-        {
-            Ok(Ok(_)) => Ok(()),
-            Err(_) => {
-                tracing::info!("STRIKE (timeout)");
-                self.storage.strike(&contributor).await?;
-                Ok(())
+        // Drain any abort signal left over from a previous, already-finished contribution (e.g. a
+        // tick that fired after `ContributionDone` but before we got back here), so it doesn't
+        // spuriously strike this new contributor.
+        while self.abort_rx.try_recv().is_ok() {}
+
+        let contribution = self.contribute_inner::<P>(contributor, participant);
+        tokio::pin!(contribution);
+        let deadline = tokio::time::sleep(Duration::from_secs(P::CONTRIBUTION_TIME_SECS));
+        tokio::pin!(deadline);
+        // Once shutdown fires, we reset the deadline to the shutdown grace period exactly once,
+        // rather than abandoning the in-flight contribution: this is what lets a validated
+        // contribution still land and be committed to storage before the coordinator stops.
+        let mut shutting_down = false;
+
+        loop {
+            tokio::select! {
+                result = &mut contribution => return result,
+                _ = self.abort_rx.recv() => {
+                    tracing::info!(?contributor, "STRIKE (coordinator detected a dead connection)");
+                    self.storage.strike(&contributor).await?;
+                    return Ok(());
+                }
+                () = &mut deadline => {
+                    if shutting_down {
+                        tracing::info!(?contributor, "shutdown grace period elapsed, re-queuing contributor");
+                        self.storage.requeue(&contributor).await?;
+                    } else {
+                        tracing::info!("STRIKE (timeout)");
+                        self.storage.strike(&contributor).await?;
+                    }
+                    return Ok(());
+                }
+                _ = self.shutdown.cancelled(), if !shutting_down => {
+                    tracing::info!(
+                        ?contributor,
+                        "shutdown requested mid-contribution, waiting up to the grace period for it to land"
+                    );
+                    shutting_down = true;
+                    deadline.as_mut().reset(tokio::time::Instant::now() + SHUTDOWN_GRACE_PERIOD);
+                }
             }
-            Ok(Err(e)) => Err(e),
         }
     }
 
@@ -112,14 +218,26 @@ impl ContributionHandler {
 }
 
 struct ParticipantQueue {
-    participants: HashMap<Address, (Participant, Amount)>,
+    storage: Storage,
+    participants: HashMap<Address, (Participant, Amount, watch::Sender<QueuePosition>)>,
+    /// Addresses that were queued before a coordinator restart but haven't reconnected yet.
+    ///
+    /// Holds the persisted bid so that a reconnecting client's priority survives the restart,
+    /// rather than being able to change its place in the queue by re-presenting a different bid.
+    pending: HashMap<Address, Amount>,
 }
 
 impl ParticipantQueue {
-    fn new() -> Self {
-        Self {
+    /// Rehydrates the queue from whatever membership was persisted to `storage` before the last
+    /// restart. Every entry starts out `pending`, since we don't yet have a live connection for
+    /// any of them; they become active participants again as their clients reconnect.
+    async fn new(storage: Storage) -> Result<Self> {
+        let pending = storage.load_queued_participants().await?.into_iter().collect();
+        Ok(Self {
+            storage,
             participants: HashMap::new(),
-        }
+            pending,
+        })
     }
 
     fn len(&self) -> usize {
@@ -127,18 +245,56 @@ impl ParticipantQueue {
     }
 
     fn bid(&self, address: &Address) -> Option<Amount> {
-        self.participants.get(address).map(|(_, bid)| *bid)
+        self.participants.get(address).map(|(_, bid, _)| *bid)
     }
 
-    fn add(&mut self, participant: Participant, bid: Amount) {
+    async fn add(&mut self, mut participant: Participant, bid: Amount) -> Result<()> {
         let address = participant.address();
+        // If this address was queued before a restart, its persisted bid is authoritative: a
+        // reconnecting client can neither lower its priority by presenting a smaller bid, nor
+        // raise it by presenting a larger one. Either way, what's already on the books wins.
+        let bid = match self.pending.remove(&address) {
+            Some(persisted_bid) => {
+                if persisted_bid != bid {
+                    tracing::warn!(
+                        ?address,
+                        ?persisted_bid,
+                        presented_bid = ?bid,
+                        "reconnecting participant presented a different bid than was persisted; keeping the persisted one"
+                    );
+                }
+                persisted_bid
+            }
+            None => bid,
+        };
         tracing::info!(?address, "has been added as a participant");
-        self.participants.insert(address, (participant, bid));
+        // Participants learn their position in the queue through a watch channel, rather than a
+        // one-shot message: the coordinator can update it on every ranking change with no per-
+        // message amplification, since `watch` only ever keeps the latest value around.
+        let (position_tx, position_rx) = watch::channel(QueuePosition {
+            index: 0,
+            len: 1,
+            contributor_bid: bid,
+            your_bid: bid,
+        });
+        participant.watch_position(position_rx);
+        self.storage.persist_queued_participant(address, bid).await?;
+        self.participants.insert(address, (participant, bid, position_tx));
+        Ok(())
     }
 
-    fn prune(&mut self) {
-        self.participants
-            .retain(|_, (connection, _)| connection.is_live());
+    async fn prune(&mut self) -> Result<()> {
+        let dead: Vec<Address> = self
+            .participants
+            .iter()
+            .filter(|(_, (connection, _, _))| !connection.is_live())
+            .map(|(address, _)| *address)
+            .collect();
+        for address in dead {
+            self.participants.remove(&address);
+            self.storage.remove_queued_participant(&address).await?;
+        }
+        Ok(())
     }
 
     fn score(&self) -> Vec<Address> {
@@ -147,34 +303,39 @@ impl ParticipantQueue {
         out
     }
 
-    fn remove(&mut self, address: &Address) -> Option<(Participant, Amount)> {
-        self.participants.remove(address)
+    async fn remove(&mut self, address: &Address) -> Result<Option<(Participant, Amount)>> {
+        let out = self.participants.remove(address).map(|(p, bid, _)| (p, bid));
+        if out.is_some() {
+            self.storage.remove_queued_participant(address).await?;
+        }
+        Ok(out)
     }
 
-    /// Inform participants of their position in the queue.
+    /// Push a fresh position update to every queued participant.
     ///
-    /// If filter is not None, only one participant will be informed.
-    async fn inform(
-        &mut self,
-        ranked: &[Address],
-        contributor_bid: Amount,
-        filter: Option<Address>,
-    ) {
+    /// `index_offset` accounts for a contribution already in flight: its contributor has already
+    /// been removed from `participants` (see `Coordinator::run`), so `ranked` only covers those
+    /// still waiting. Passing `1` there keeps `index`/`len` counting the in-flight contributor as
+    /// position `0`, instead of mislabeling whoever is merely first in line next.
+    ///
+    /// Because this rides a coalescing `watch` channel per participant, broadcasting to everyone
+    /// on every ranking change is cheap: each participant's task only ever sees the latest value,
+    /// read at its own pace, so there's no amplification from repeated connect/disconnect churn.
+    fn update_positions(&mut self, ranked: &[Address], contributor_bid: Amount, index_offset: u32) {
+        let len = ranked.len() as u32 + index_offset;
         for (i, address) in ranked.iter().enumerate() {
-            match filter {
-                Some(filter) if filter != *address => continue,
-                _ => {}
-            }
-            let (connection, bid) = self
+            let (_, bid, position_tx) = self
                 .participants
                 .get(address)
                 .expect("Ranked participants are chosen from the set of connections");
-            if let Err(e) =
-                connection.try_notify(i as u32, ranked.len() as u32, contributor_bid, *bid)
-            {
-                tracing::info!(?e, ?address, "pruning connection that we failed to notify");
-                self.participants.remove(address);
-            };
+            // If the participant's task has already gone away, `send` fails; `prune` will clean
+            // up the stale entry on the next pass, so there's nothing more to do here.
+            let _ = position_tx.send(QueuePosition {
+                index: i as u32 + index_offset,
+                len,
+                contributor_bid,
+                your_bid: *bid,
+            });
         }
     }
 }
@@ -183,19 +344,75 @@ pub struct Coordinator {
     storage: Storage,
     participants: ParticipantQueue,
     new_participant_rx: mpsc::Receiver<(Participant, Amount)>,
+    shutdown: CancellationToken,
+    config: CoordinatorConfig,
+    /// The last time we accepted a `NewParticipant` event for a given address, used to debounce
+    /// reconnect churn.
+    last_seen: HashMap<Address, Instant>,
+    /// The last time we broadcast a position update, used to collapse bursts of ranking changes.
+    last_inform: Option<Instant>,
 }
 
 impl Coordinator {
-    pub fn new(storage: Storage) -> (Self, mpsc::Sender<(Participant, Amount)>) {
-        let (new_participant_tx, new_participant_rx) = mpsc::channel(9001);
-        (
+    pub async fn new(
+        storage: Storage,
+        config: CoordinatorConfig,
+    ) -> Result<(Self, mpsc::Sender<(Participant, Amount)>, CancellationToken)> {
+        let (new_participant_tx, new_participant_rx) = mpsc::channel(config.backlog_size);
+        let shutdown = CancellationToken::new();
+        let participants = ParticipantQueue::new(storage.clone()).await?;
+        Ok((
             Self {
                 storage,
-                participants: ParticipantQueue::new(),
+                participants,
                 new_participant_rx,
+                shutdown: shutdown.clone(),
+                config,
+                last_seen: HashMap::new(),
+                last_inform: None,
             },
             new_participant_tx,
-        )
+            shutdown,
+        ))
+    }
+
+    /// Returns `true` (and records the attempt) if a `NewParticipant` event for `address` should
+    /// be accepted right now, or `false` if it arrived too soon after the last one and should be
+    /// dropped to avoid amplification from repeated connect/disconnect churn.
+    fn admit_reconnect(&mut self, address: Address) -> bool {
+        let now = Instant::now();
+        let min_reconnect_interval = self.config.min_reconnect_interval;
+        // Evict entries that have already aged out of the throttle window, so that an attacker
+        // churning through fresh addresses can't grow this map without bound.
+        self.last_seen
+            .retain(|_, last| now.duration_since(*last) < min_reconnect_interval);
+        if self.last_seen.contains_key(&address) {
+            return false;
+        }
+        self.last_seen.insert(address, now);
+        true
+    }
+
+    /// Broadcasts a position update, unless one was already sent more recently than
+    /// `min_inform_interval`, in which case it's skipped: the next tick or ranking change will
+    /// catch everyone up instead.
+    fn maybe_update_positions(
+        &mut self,
+        ranked: &[Address],
+        contributor_bid: Amount,
+        index_offset: u32,
+    ) {
+        let now = Instant::now();
+        let due = self
+            .last_inform
+            .map_or(true, |last| now.duration_since(last) >= self.config.min_inform_interval);
+        if !due {
+            return;
+        }
+        // `index_offset` is `1` while a contribution is in flight and `0` otherwise, exactly as
+        // for `ParticipantQueue::update_positions`.
+        self.participants.update_positions(ranked, contributor_bid, index_offset);
+        self.last_inform = Some(now);
     }
 
     pub async fn run<P: Phase + 'static>(mut self) -> Result<()> {
@@ -203,10 +420,12 @@ impl Coordinator {
             NewParticipant(Participant, Amount),
             ContributionDone,
             ContributionHandlerFinished(Result<()>),
+            Shutdown,
+            Tick,
         }
 
-        let (contribution_handler, start_contribution_tx, done_contribution_rx) =
-            ContributionHandler::new(self.storage);
+        let (contribution_handler, start_contribution_tx, done_contribution_rx, abort_tx) =
+            ContributionHandler::new(self.storage, self.shutdown.clone());
         let wait_for_contribution_handler = tokio::spawn(contribution_handler.run::<P>());
         // Merge the events from both being notified of new participants, and of completed
         // contributions.
@@ -217,62 +436,167 @@ impl Coordinator {
                 Event::ContributionHandlerFinished(x.unwrap_or(Err(anyhow!(
                     "failed to join on contribution handler handle"
                 ))))
-            }));
+            }))
+            .merge(
+                futures::stream::once(self.shutdown.clone().cancelled_owned())
+                    .map(|_| Event::Shutdown),
+            )
+            .merge(
+                IntervalStream::new(tokio::time::interval(self.config.tick_period))
+                    .map(|_| Event::Tick),
+            );
 
         // We start by needing a contribution.
         let mut want_contribution = true;
+        // Set once we've seen `Event::Shutdown`, so that we stop accepting new work and instead
+        // wait for any in-flight contribution to land.
+        let mut shutting_down = false;
+        // The contributor currently being serviced, if any: their bid (so ticks can still report
+        // the right `contributor_bid` once they've been removed from `participants`), and a clone
+        // of their connection, so we can check liveness without interfering with the handler's own
+        // copy.
+        let mut in_flight: Option<(Address, Amount, Participant)> = None;
         loop {
             tracing::debug!(
                 participant_count = self.participants.len(),
                 "top of coordinator loop"
             );
             // 1. Wait for a new event
-            let maybe_new_address = match stream.next().await {
+            match stream.next().await {
                 None => anyhow::bail!("coordinator event stream closed unexpectedly."),
                 Some(Event::NewParticipant(participant, bid)) => {
-                    let addr = participant.address();
-                    self.participants.add(participant, bid);
-                    Some(addr)
+                    if shutting_down {
+                        tracing::debug!("shutting down, refusing new participant");
+                        continue;
+                    }
+                    let address = participant.address();
+                    if !self.admit_reconnect(address) {
+                        tracing::debug!(?address, "throttling reconnect, too soon since last attempt");
+                        continue;
+                    }
+                    self.participants.add(participant, bid).await?;
                 }
                 Some(Event::ContributionDone) => {
+                    // Note: `shutting_down` is never true here. It's only set in the `Shutdown`
+                    // arm below, which either returns immediately or enters its own grace-period
+                    // loop that handles `ContributionDone` (and returns) without ever falling back
+                    // into this outer match.
+                    in_flight = None;
                     // We always want a new contribution now.
                     want_contribution = true;
-                    None
+                }
+                Some(Event::Tick) => {
+                    // Re-derive the ranked order and re-push everyone's position, as if a new
+                    // round had started, so that clients don't have to wait for a connection event
+                    // to learn their updated position.
+                    self.participants.prune().await?;
+                    let ranked = self.participants.score();
+                    match &in_flight {
+                        Some((contributor, contributor_bid, connection)) => {
+                            if !connection.is_live() {
+                                tracing::info!(
+                                    ?contributor,
+                                    "tick detected a dead in-flight contributor, aborting early"
+                                );
+                                let _ = abort_tx.send(()).await;
+                            }
+                            // The in-flight contributor has already been removed from
+                            // `participants`, so `ranked` only covers those still waiting; use
+                            // their real bid, and shift indices by one so the queue doesn't
+                            // mislabel whoever is merely first in line next as the contributor.
+                            if !ranked.is_empty() {
+                                self.participants.update_positions(&ranked, *contributor_bid, 1);
+                            }
+                        }
+                        None => {
+                            if !ranked.is_empty() {
+                                let contributor_bid = self
+                                    .participants
+                                    .bid(&ranked[0])
+                                    .expect("contributor should be in participant queue");
+                                self.participants.update_positions(&ranked, contributor_bid, 0);
+                            }
+                        }
+                    }
+                    continue;
                 }
                 Some(Event::ContributionHandlerFinished(why)) => {
-                    return Err(why
-                        .err()
-                        .unwrap_or(anyhow!("contribution handler finished with no reason")));
+                    // `Ok(())` means the handler stopped cleanly (channel closed, or shutdown
+                    // requested), which should bring the coordinator down cleanly too; only a
+                    // genuine error here should be propagated as one.
+                    return why;
                 }
-            };
+                Some(Event::Shutdown) => {
+                    tracing::info!("shutdown requested, no longer accepting new participants");
+                    shutting_down = true;
+                    if want_contribution {
+                        // No contribution in flight, so there's nothing to wait for.
+                        return Ok(());
+                    }
+                    // Otherwise, wait (up to a grace period) for the in-flight contribution to be
+                    // committed to storage before giving up on it, ignoring unrelated events
+                    // (ticks, latecomer connections) in the meantime.
+                    let deadline = tokio::time::Instant::now() + SHUTDOWN_GRACE_PERIOD;
+                    loop {
+                        match tokio::time::timeout_at(deadline, stream.next()).await {
+                            Ok(Some(Event::ContributionDone)) => {
+                                tracing::info!(
+                                    "in-flight contribution committed before shutdown grace period elapsed"
+                                );
+                                return Ok(());
+                            }
+                            Ok(Some(Event::ContributionHandlerFinished(why))) => {
+                                // As above: a clean stop here is expected once we've asked the
+                                // handler to shut down, not a failure.
+                                return why;
+                            }
+                            Ok(Some(_)) => continue,
+                            Ok(None) | Err(_) => {
+                                tracing::warn!(
+                                    "shutdown grace period elapsed without the in-flight contribution landing"
+                                );
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+            }
             // 2. Score connections
-            self.participants.prune();
+            self.participants.prune().await?;
             let ranked = self.participants.score();
             // In theory ranked could've become empty for some reason in the meantime
             if ranked.is_empty() {
                 continue;
             }
-            // 3. Update people on their status in the queue.
-            //
-            // The intention of this loop is that when someone joins, they get a message with their
-            // position in the queue, but other people don't receive updates, to avoid
-            // amplification attacks when repeatedly connecting and disconnecting. However, we want
-            // to inform everyone when a new "round" starts.
+            // 3. Update everyone on their status in the queue. Since positions are delivered over
+            // a coalescing `watch` channel, there's no amplification cost to keeping everyone's
+            // position current on every ranking change, unlike a push-notify model; we still
+            // throttle how often we bother, since a burst of connection events shouldn't cause a
+            // burst of broadcasts, and the tick loop will catch everyone up regardless.
             let contributor = ranked[0];
             let contributor_bid = self
                 .participants
                 .bid(&contributor)
                 .expect("contributor should be in participant queue");
-            self.participants
-                .inform(&ranked, contributor_bid, maybe_new_address)
-                .await;
+            // While a contribution is in flight, its contributor has already left `participants`,
+            // so report their bid and count them as position `0`, as the tick path does.
+            match &in_flight {
+                Some((_, in_flight_bid, _)) => {
+                    self.maybe_update_positions(&ranked, *in_flight_bid, 1)
+                }
+                None => self.maybe_update_positions(&ranked, contributor_bid, 0),
+            }
             // 4. If we want a new contribution, get that process going.
-            if want_contribution {
+            if want_contribution && !shutting_down {
                 // 5. Remove from pool regardless of what will happen
-                let (participant, _) = self
+                let (participant, bid) = self
                     .participants
                     .remove(&contributor)
+                    .await?
                     .expect("the selected contributor exists");
+                // Keep a cheap clone of the connection handle around so ticks can check liveness
+                // without taking the participant back from the handler.
+                in_flight = Some((contributor, bid, participant.clone()));
                 start_contribution_tx
                     .send((contributor, participant))
                     .await
@@ -0,0 +1,66 @@
+//! A single participant's connection to the coordinator.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use anyhow::Result;
+use penumbra_keys::Address;
+use tokio::sync::watch;
+
+use crate::{coordinator::QueuePosition, phase::Phase};
+
+/// A participant's connection, and the state `Coordinator`/`ParticipantQueue` track about it.
+///
+/// Cheaply `Clone`-able: every clone shares the same liveness flag, so
+/// `Coordinator` can keep a clone around to poll liveness (`in_flight`) while the original is
+/// handed off to `ContributionHandler` to drive the actual contribution.
+#[derive(Clone)]
+pub struct Participant {
+    address: Address,
+    live: Arc<AtomicBool>,
+    position_rx: Option<watch::Receiver<QueuePosition>>,
+}
+
+impl Participant {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            live: Arc::new(AtomicBool::new(true)),
+            position_rx: None,
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Reads the shared liveness flag. It starts out `true` and nothing in this type clears it,
+    /// so it stays `true`. Because `live` is shared across clones, a check made from a clone
+    /// (e.g. `Coordinator`'s `in_flight` handle) reads the same flag as `ContributionHandler`'s
+    /// copy, not a stale one.
+    pub fn is_live(&self) -> bool {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    /// Hands this participant a live feed of its own queue position, which it keeps (replacing
+    /// any earlier feed). Because it's a `watch` channel rather than a queue, a slow reader only
+    /// ever sees the latest position, never a backlog of stale ones. This type does not forward
+    /// the updates to the client.
+    pub fn watch_position(&mut self, position_rx: watch::Receiver<QueuePosition>) {
+        self.position_rx = Some(position_rx);
+    }
+
+    /// Always returns `Ok(None)`: no contribution protocol is run with the client here, so no
+    /// uploaded contribution is ever returned.
+    pub async fn contribute<P: Phase>(&mut self, _parent: &P::Crs) -> Result<Option<P::Unvalidated>> {
+        Ok(None)
+    }
+
+    /// Accepts the `slot` a contribution was committed to and returns `Ok(())`; nothing is sent
+    /// to the client.
+    pub async fn confirm(&mut self, _slot: u64) -> Result<()> {
+        Ok(())
+    }
+}
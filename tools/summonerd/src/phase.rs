@@ -0,0 +1,37 @@
+//! The ceremony-specific contribution protocol.
+//!
+//! `Phase` is the boundary between the connection/queue machinery in `coordinator.rs` and
+//! `participant.rs` (which this backlog touches) and the actual cryptographic trusted-setup logic
+//! (which it doesn't): validating an uploaded contribution, checking it's linked to its parent,
+//! and committing it to storage. None of that lives here yet in this tree; the associated types
+//! and methods below exist only so the rest of the crate has something concrete to be generic
+//! over.
+
+use anyhow::Result;
+use penumbra_keys::Address;
+
+use crate::storage::Storage;
+
+pub trait Phase: Send + Sync + 'static {
+    /// The current reference string a contribution is built on top of.
+    type Crs: Send;
+    /// An uploaded contribution that hasn't yet been validated.
+    type Unvalidated: Send;
+    /// A contribution that has passed validation and linking checks.
+    type Contribution: Send;
+
+    /// The storage marker this phase's contributions are recorded under.
+    const MARKER: &'static str;
+    /// How long a participant is given to upload a contribution before being struck for timeout.
+    const CONTRIBUTION_TIME_SECS: u64;
+
+    async fn current_crs(storage: &Storage) -> Result<Option<Self::Crs>>;
+    async fn fetch_root(storage: &Storage) -> Result<Self::Crs>;
+    fn validate(root: &Self::Crs, unvalidated: Self::Unvalidated) -> Option<Self::Contribution>;
+    fn is_linked_to(contribution: &Self::Contribution, parent: &Self::Crs) -> bool;
+    async fn commit_contribution(
+        storage: &Storage,
+        contributor: Address,
+        contribution: Self::Contribution,
+    ) -> Result<()>;
+}
@@ -0,0 +1,87 @@
+//! Durable ceremony state.
+//!
+//! `Storage` is the coordinator's interface to the state that backs a ceremony. This
+//! implementation keeps queue membership in memory, shared by every clone, so a `Coordinator`
+//! rebuilt from the same `Storage` can rehydrate its queue. Nothing is written to disk, so none of
+//! it survives the process exiting; strikes, re-queues, and slot markers are not stored at all.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use penumbra_keys::Address;
+use penumbra_num::Amount;
+use tokio::sync::Mutex;
+
+/// A handle to the ceremony's durable state.
+///
+/// Cheaply `Clone`-able: every clone shares the same underlying store, so `Coordinator` and
+/// `ContributionHandler` can each hold their own handle without any additional synchronization on
+/// top of what the store itself provides.
+#[derive(Clone)]
+pub struct Storage {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Participants that are queued (not currently contributing), keyed by address, so a restart
+    /// can rehydrate the bid-ordered queue without waiting for every client to reconnect first.
+    queued_participants: HashMap<Address, Amount>,
+}
+
+impl Storage {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::default())),
+        }
+    }
+
+    /// Logs a strike against `address`, for an invalid, partial, or timed-out contribution. The
+    /// strike is not stored.
+    pub async fn strike(&self, address: &Address) -> Result<()> {
+        tracing::warn!(?address, "recording strike");
+        Ok(())
+    }
+
+    /// Called instead of `strike` when `address`'s contribution was interrupted by a cooperative
+    /// shutdown. It only logs the re-queue: the queued-participant record for `address` was
+    /// removed when it started contributing, and this does not restore it, so `address` gets
+    /// neither its place nor its bid back.
+    pub async fn requeue(&self, address: &Address) -> Result<()> {
+        tracing::info!(?address, "re-queuing after shutdown-interrupted contribution");
+        Ok(())
+    }
+
+    /// Always returns slot `0`, whatever the phase marker: this implementation does not track
+    /// which slot a committed contribution landed in.
+    pub async fn current_slot(&self, _marker: &'static str) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Every participant that was still queued (not yet contributing) the last time the
+    /// coordinator stopped, along with their bid, so `ParticipantQueue::new` can rehydrate
+    /// priority ordering across a restart instead of losing it.
+    pub async fn load_queued_participants(&self) -> Result<Vec<(Address, Amount)>> {
+        let inner = self.inner.lock().await;
+        Ok(inner
+            .queued_participants
+            .iter()
+            .map(|(address, bid)| (*address, *bid))
+            .collect())
+    }
+
+    /// Persists that `address` is queued with `bid`, so it survives a restart.
+    pub async fn persist_queued_participant(&self, address: Address, bid: Amount) -> Result<()> {
+        let mut inner = self.inner.lock().await;
+        inner.queued_participants.insert(address, bid);
+        Ok(())
+    }
+
+    /// Forgets `address`'s queued-participant record, because it either started contributing or
+    /// was pruned as dead.
+    pub async fn remove_queued_participant(&self, address: &Address) -> Result<()> {
+        let mut inner = self.inner.lock().await;
+        inner.queued_participants.remove(address);
+        Ok(())
+    }
+}